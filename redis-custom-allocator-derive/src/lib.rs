@@ -0,0 +1,81 @@
+//! The `#[derive(MemoryConsumption)]` proc macro for `redis-custom-allocator`.
+//!
+//! Hand-implementing `MemoryConsumption` for every nested struct is
+//! error-prone: it's easy to forget a field, or to forget to subtract a
+//! field's inline size and double-count it. This macro generates the
+//! obvious field-by-field sum instead.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Index};
+
+/// Derives `MemoryConsumption` for a struct by summing `size_of::<Self>()`
+/// with each field's recursive `memory_consumption()`, minus that field's
+/// own inline size (to avoid counting it twice).
+#[proc_macro_derive(MemoryConsumption)]
+pub fn derive_memory_consumption(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    // Every type parameter has to carry `MemoryConsumption` itself, since
+    // the generated body recurses into `memory_consumption()` on fields of
+    // that type. `split_for_impl()` alone doesn't add this bound, so a
+    // generic struct's derive would otherwise fail to compile (E0599) the
+    // moment a field uses one of the struct's own type parameters.
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::redis_custom_allocator::MemoryConsumption));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let data = match &input.data {
+        Data::Struct(data) => data,
+        _ => {
+            return syn::Error::new_spanned(
+                &input.ident,
+                "`MemoryConsumption` can only be derived for structs",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    let field_terms = match &data.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let ident = field.ident.as_ref().unwrap();
+                let ty = &field.ty;
+                quote! {
+                    + (self.#ident.memory_consumption().saturating_sub(::std::mem::size_of::<#ty>()))
+                }
+            })
+            .collect::<Vec<_>>(),
+        Fields::Unnamed(fields) => fields
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let index = Index::from(i);
+                let ty = &field.ty;
+                quote! {
+                    + (self.#index.memory_consumption().saturating_sub(::std::mem::size_of::<#ty>()))
+                }
+            })
+            .collect::<Vec<_>>(),
+        Fields::Unit => Vec::new(),
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::redis_custom_allocator::MemoryConsumption for #name #ty_generics #where_clause {
+            fn memory_consumption(&self) -> usize {
+                ::std::mem::size_of::<Self>() #(#field_terms)*
+            }
+        }
+    };
+
+    expanded.into()
+}
@@ -0,0 +1,95 @@
+//! A bridge from [`CustomAllocator`] to [`std::alloc::GlobalAlloc`].
+//!
+//! Stable Rust only lets you install a process-wide allocator through
+//! `#[global_allocator]` and the `GlobalAlloc` trait; the unstable
+//! `allocator_api` feature isn't available there, and a
+//! [`CustomAllocator`] can't be installed that way on its own.
+//! [`AsGlobal`] closes that gap by adapting any
+//! [`CustomAllocator`] into a `GlobalAlloc`, at the cost of turning
+//! allocation failure into a null pointer the way `GlobalAlloc` expects.
+
+use crate::CustomAllocator;
+use std::alloc::{GlobalAlloc, Layout};
+use std::ptr::NonNull;
+
+/// Adapts a [`CustomAllocator`] into a [`GlobalAlloc`], so it can be
+/// installed with `#[global_allocator]`.
+///
+/// Allocation errors reported by the inner allocator are translated into
+/// a null pointer, since `GlobalAlloc` has no other way to signal
+/// failure.
+pub struct AsGlobal<A>(pub A);
+
+// SAFETY: `AsGlobal` upholds the `GlobalAlloc` contract by forwarding
+// every call to the inner `CustomAllocator`, which is required to uphold
+// the equivalent `CustomAllocator` contract.
+unsafe impl<A: CustomAllocator> GlobalAlloc for AsGlobal<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.0.allocate(layout) {
+            Ok(ptr) => ptr.as_ptr().cast::<u8>(),
+            Err(_) => std::ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(ptr) = NonNull::new(ptr) {
+            // SAFETY: forwarding this call's own safety contract to the
+            // inner allocator.
+            unsafe {
+                self.0.deallocate(ptr, layout);
+            }
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        match self.0.allocate_zeroed(layout) {
+            Ok(ptr) => ptr.as_ptr().cast::<u8>(),
+            Err(_) => std::ptr::null_mut(),
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Some(ptr) = NonNull::new(ptr) else {
+            return std::ptr::null_mut();
+        };
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
+            return std::ptr::null_mut();
+        };
+
+        // SAFETY: `ptr`/`layout` were handed to us by the caller per the
+        // `GlobalAlloc::realloc` contract, which matches the safety
+        // requirements of `grow`/`shrink`.
+        let result = unsafe {
+            if new_size >= layout.size() {
+                self.0.grow(ptr, layout, new_layout)
+            } else {
+                self.0.shrink(ptr, layout, new_layout)
+            }
+        };
+
+        match result {
+            Ok(new_ptr) => new_ptr.as_ptr().cast::<u8>(),
+            // The allocator couldn't resize in place (or at all); fall
+            // back to allocating fresh memory and copying over.
+            Err(_) => match self.0.allocate(new_layout) {
+                Ok(new_ptr) => {
+                    let new_ptr = new_ptr.as_ptr().cast::<u8>();
+                    // SAFETY: `ptr` is valid for reads of `layout.size()`
+                    // bytes, `new_ptr` is a fresh, non-overlapping
+                    // allocation of at least `new_size` bytes, and we copy
+                    // the smaller of the two sizes.
+                    unsafe {
+                        std::ptr::copy_nonoverlapping(
+                            ptr.as_ptr(),
+                            new_ptr,
+                            layout.size().min(new_size),
+                        );
+                        self.0.deallocate(ptr, layout);
+                    }
+                    new_ptr
+                }
+                Err(_) => std::ptr::null_mut(),
+            },
+        }
+    }
+}
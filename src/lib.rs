@@ -7,6 +7,20 @@
 
 use std::{alloc::{Layout, GlobalAlloc}, ptr::NonNull};
 
+pub mod page_allocator;
+pub use page_allocator::PageAllocator;
+
+pub mod raw_vec;
+pub mod vec;
+pub use raw_vec::{RawVec, TryReserveError};
+pub use vec::Vec;
+
+pub mod global;
+pub use global::AsGlobal;
+
+pub mod tracking;
+pub use tracking::TrackingAllocator;
+
 /// This trait is almost a drop-in copy of the [`std::alloc::Allocator`]
 /// trait.
 pub trait CustomAllocator {
@@ -116,6 +130,12 @@ pub trait CustomAllocator {
             "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
         );
 
+        // SAFETY: `ptr`/`old_layout`/`new_layout` satisfy `grow_in_place`'s
+        // safety contract, which is identical to this method's.
+        if let Ok(new_ptr) = unsafe { self.grow_in_place(ptr, old_layout, new_layout) } {
+            return Ok(new_ptr);
+        }
+
         let mut new_ptr = self.allocate(new_layout)?;
 
         // SAFETY: because `new_layout.size()` must be greater than or equal to
@@ -135,6 +155,35 @@ pub trait CustomAllocator {
         Ok(new_ptr)
     }
 
+    /// Attempts to extend the memory block referenced by `ptr` in place,
+    /// without moving it.
+    ///
+    /// This is purely an optimization hook for allocators (slabs, arenas,
+    /// segregated free lists) that can sometimes satisfy a grow request
+    /// without relocating the block. The default implementation always
+    /// declines.
+    ///
+    /// # Safety
+    ///
+    /// Same preconditions as [`Self::grow`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(())` if the block cannot be extended in place; this is
+    /// not a hard failure, it simply means the caller (by default,
+    /// [`Self::grow`]/[`Self::grow_zeroed`]) should fall back to
+    /// allocating a new block and copying.
+    #[allow(clippy::result_unit_err)] // `()` is a deliberate "declined" sentinel, not an error type.
+    unsafe fn grow_in_place(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, ()> {
+        let _ = (ptr, old_layout, new_layout);
+        Err(())
+    }
+
     /// Behaves like [`Self::grow`], but also ensures that the new contents are set to zero before being
     /// returned.
     ///
@@ -183,6 +232,19 @@ pub trait CustomAllocator {
             "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
         );
 
+        // SAFETY: `ptr`/`old_layout`/`new_layout` satisfy `grow_in_place`'s
+        // safety contract, which is identical to this method's.
+        if let Ok(mut new_ptr) = unsafe { self.grow_in_place(ptr, old_layout, new_layout) } {
+            // SAFETY: `grow_in_place` guarantees `new_ptr` is valid for
+            // writes for its whole (possibly larger-than-requested)
+            // length, and bytes before `old_layout.size()` must be left
+            // untouched by the allocator.
+            unsafe {
+                new_ptr.as_mut()[old_layout.size()..].fill(0);
+            }
+            return Ok(new_ptr);
+        }
+
         let mut new_ptr = self.allocate_zeroed(new_layout)?;
 
         // SAFETY: because `new_layout.size()` must be greater than or equal to
@@ -251,6 +313,12 @@ pub trait CustomAllocator {
             "`new_layout.size()` must be smaller than or equal to `old_layout.size()`"
         );
 
+        // SAFETY: `ptr`/`old_layout`/`new_layout` satisfy `shrink_in_place`'s
+        // safety contract, which is identical to this method's.
+        if let Ok(new_ptr) = unsafe { self.shrink_in_place(ptr, old_layout, new_layout) } {
+            return Ok(new_ptr);
+        }
+
         let mut new_ptr = self.allocate(new_layout)?;
 
         // SAFETY: because `new_layout.size()` must be lower than or equal to
@@ -270,6 +338,35 @@ pub trait CustomAllocator {
         Ok(new_ptr)
     }
 
+    /// Attempts to shrink the memory block referenced by `ptr` in place,
+    /// without moving it.
+    ///
+    /// This is purely an optimization hook for allocators (slabs, arenas,
+    /// segregated free lists) that can sometimes satisfy a shrink request
+    /// without relocating the block. The default implementation always
+    /// declines.
+    ///
+    /// # Safety
+    ///
+    /// Same preconditions as [`Self::shrink`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(())` if the block cannot be shrunk in place; this is
+    /// not a hard failure, it simply means the caller (by default,
+    /// [`Self::shrink`]) should fall back to allocating a new, smaller
+    /// block and copying.
+    #[allow(clippy::result_unit_err)] // `()` is a deliberate "declined" sentinel, not an error type.
+    unsafe fn shrink_in_place(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, ()> {
+        let _ = (ptr, old_layout, new_layout);
+        Err(())
+    }
+
     /// Creates a "by reference" adapter for this instance of
     /// [`CustomAllocator`].
     ///
@@ -359,3 +456,131 @@ pub trait MemoryConsumption {
     /// to the heap-allocated memory, the capacity, etc.
     fn memory_consumption(&self) -> usize;
 }
+
+#[cfg(feature = "derive")]
+pub use redis_custom_allocator_derive::MemoryConsumption;
+
+macro_rules! impl_memory_consumption_for_scalar {
+    ($($ty:ty),+ $(,)?) => {
+        $(
+            impl MemoryConsumption for $ty {
+                fn memory_consumption(&self) -> usize {
+                    std::mem::size_of::<Self>()
+                }
+            }
+        )+
+    };
+}
+
+impl_memory_consumption_for_scalar!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+);
+
+impl<T: MemoryConsumption> MemoryConsumption for Box<T> {
+    fn memory_consumption(&self) -> usize {
+        // `self` itself is just a pointer; `T`'s own inline size plus
+        // whatever it owns on the heap lives behind it.
+        std::mem::size_of::<Self>() + (**self).memory_consumption()
+    }
+}
+
+impl<T: MemoryConsumption> MemoryConsumption for Option<T> {
+    fn memory_consumption(&self) -> usize {
+        // `size_of::<Self>()` already covers `T`'s inline storage (and the
+        // discriminant, if any), so only the heap bytes `T` owns beyond
+        // its own inline size are added on top.
+        std::mem::size_of::<Self>()
+            + match self {
+                Some(value) => value
+                    .memory_consumption()
+                    .saturating_sub(std::mem::size_of::<T>()),
+                None => 0,
+            }
+    }
+}
+
+impl MemoryConsumption for String {
+    fn memory_consumption(&self) -> usize {
+        std::mem::size_of::<Self>() + self.capacity()
+    }
+}
+
+impl<T: MemoryConsumption> MemoryConsumption for std::vec::Vec<T> {
+    fn memory_consumption(&self) -> usize {
+        let inline_per_element = std::mem::size_of::<T>();
+        std::mem::size_of::<Self>()
+            + self.capacity() * inline_per_element
+            + self
+                .iter()
+                .map(|item| item.memory_consumption().saturating_sub(inline_per_element))
+                .sum::<usize>()
+    }
+}
+
+impl<K, V, S> MemoryConsumption for std::collections::HashMap<K, V, S>
+where
+    K: MemoryConsumption,
+    V: MemoryConsumption,
+{
+    fn memory_consumption(&self) -> usize {
+        let inline_per_entry = std::mem::size_of::<K>() + std::mem::size_of::<V>();
+        std::mem::size_of::<Self>()
+            + self.capacity() * inline_per_entry
+            + self
+                .iter()
+                .map(|(key, value)| {
+                    (key.memory_consumption().saturating_sub(std::mem::size_of::<K>()))
+                        + (value.memory_consumption().saturating_sub(std::mem::size_of::<V>()))
+                })
+                .sum::<usize>()
+    }
+}
+
+impl<K: MemoryConsumption, V: MemoryConsumption> MemoryConsumption
+    for std::collections::BTreeMap<K, V>
+{
+    fn memory_consumption(&self) -> usize {
+        // `BTreeMap` doesn't expose its node capacity, so each entry's
+        // full consumption (inline size included) is used as an
+        // approximation of the per-entry node storage.
+        std::mem::size_of::<Self>()
+            + self
+                .iter()
+                .map(|(key, value)| key.memory_consumption() + value.memory_consumption())
+                .sum::<usize>()
+    }
+}
+
+macro_rules! impl_memory_consumption_for_tuple {
+    ($($T:ident : $idx:tt),+) => {
+        impl<$($T: MemoryConsumption),+> MemoryConsumption for ($($T,)+) {
+            fn memory_consumption(&self) -> usize {
+                std::mem::size_of::<Self>()
+                    $(+ self.$idx.memory_consumption().saturating_sub(std::mem::size_of::<$T>()))+
+            }
+        }
+    };
+}
+
+impl_memory_consumption_for_tuple!(A:0);
+impl_memory_consumption_for_tuple!(A:0, B:1);
+impl_memory_consumption_for_tuple!(A:0, B:1, C:2);
+impl_memory_consumption_for_tuple!(A:0, B:1, C:2, D:3);
+impl_memory_consumption_for_tuple!(A:0, B:1, C:2, D:3, E:4);
+impl_memory_consumption_for_tuple!(A:0, B:1, C:2, D:3, E:4, F:5);
@@ -0,0 +1,529 @@
+//! An OS-backed bump allocator for fragile allocation contexts.
+//!
+//! [`PageAllocator`] reserves memory straight from the operating system
+//! (`mmap`/`MAP_ANON` on Unix, `VirtualAlloc` on Windows) in page-aligned
+//! chunks and bumps a cursor within the current chunk on every
+//! [`allocate`][CustomAllocator::allocate] call. It never touches the
+//! global allocator, which makes it safe to use from contexts where that
+//! allocator's lock may already be held on the same thread -- most
+//! notably Redis' crash and signal handlers.
+//!
+//! Individual calls to [`deallocate`][CustomAllocator::deallocate] are
+//! no-ops, since a bump allocator can only free everything at once; every
+//! chunk reserved from the OS is unmapped in [`Drop`].
+
+use crate::CustomAllocator;
+use std::cell::Cell;
+use std::fmt;
+use std::ptr::NonNull;
+
+/// The default size, in bytes, of a single OS reservation.
+///
+/// This is rounded up to a whole number of OS pages when a chunk is
+/// actually reserved.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Errors produced by [`PageAllocator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageAllocatorError {
+    /// The operating system refused to reserve the requested pages (e.g.
+    /// `mmap`/`VirtualAlloc` returned an error).
+    ReservationFailed,
+    /// Satisfying the allocation would have required reserving more
+    /// memory than the allocator's configured maximum.
+    MaxReservationExceeded {
+        /// The number of bytes that would have had to be reserved.
+        requested: usize,
+        /// The configured maximum reservation, in bytes.
+        max: usize,
+    },
+}
+
+impl fmt::Display for PageAllocatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReservationFailed => {
+                write!(f, "failed to reserve memory from the operating system")
+            }
+            Self::MaxReservationExceeded { requested, max } => write!(
+                f,
+                "allocation would require reserving {requested} bytes, which exceeds the configured maximum of {max} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PageAllocatorError {}
+
+/// Header placed at the start of every OS-reserved chunk. Chunks form an
+/// intrusive singly-linked list, newest first, so the allocator itself
+/// only needs to remember the head.
+#[repr(C)]
+struct ChunkHeader {
+    /// The previously reserved chunk, if any.
+    next: Option<NonNull<ChunkHeader>>,
+    /// The address returned by the OS for this chunk's reservation.
+    mapping_start: usize,
+    /// The total number of bytes reserved from the OS for this chunk,
+    /// header included. Needed to unmap the chunk on drop.
+    mapping_size: usize,
+    /// The address one past the last usable byte in this chunk.
+    data_end: usize,
+    /// The address of the next free byte in this chunk.
+    cursor: usize,
+    /// The address and size of the most recent allocation handed out from
+    /// this chunk, if it can still be grown in place.
+    last_alloc: Option<(usize, usize)>,
+}
+
+/// A bump allocator that reserves its memory directly from the operating
+/// system in fixed-size pages, growable to fit oversized requests.
+///
+/// See the [module documentation](self) for the rationale.
+pub struct PageAllocator {
+    head: Cell<Option<NonNull<ChunkHeader>>>,
+    default_chunk_size: usize,
+    max_reservation: Option<usize>,
+}
+
+impl PageAllocator {
+    /// Creates a new allocator using [`DEFAULT_CHUNK_SIZE`] as the default
+    /// chunk size and no maximum reservation cap.
+    pub fn new() -> Self {
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Creates a new allocator that reserves chunks of at least
+    /// `default_chunk_size` bytes at a time.
+    pub fn with_chunk_size(default_chunk_size: usize) -> Self {
+        Self {
+            head: Cell::new(None),
+            default_chunk_size,
+            max_reservation: None,
+        }
+    }
+
+    /// Sets the maximum number of bytes a single OS reservation may span.
+    ///
+    /// Requests that would need a bigger chunk fail with
+    /// [`PageAllocatorError::MaxReservationExceeded`] instead of reserving
+    /// it.
+    pub fn with_max_reservation(mut self, max_reservation: usize) -> Self {
+        self.max_reservation = Some(max_reservation);
+        self
+    }
+
+    /// Reserves a fresh chunk of at least `min_size` bytes, its
+    /// [`ChunkHeader`] included, and links it in as the new head.
+    fn reserve_chunk(&self, min_size: usize) -> Result<NonNull<ChunkHeader>, PageAllocatorError> {
+        let page_size = os::page_size();
+        let wanted = min_size.max(self.default_chunk_size);
+        let mapping_size = round_up(wanted, page_size);
+
+        if let Some(max) = self.max_reservation {
+            if mapping_size > max {
+                return Err(PageAllocatorError::MaxReservationExceeded {
+                    requested: mapping_size,
+                    max,
+                });
+            }
+        }
+
+        let mapping_start =
+            os::reserve(mapping_size).ok_or(PageAllocatorError::ReservationFailed)?;
+
+        let header_ptr = mapping_start.as_ptr().cast::<ChunkHeader>();
+        // SAFETY: `mapping_start` is a fresh OS reservation at least
+        // `mapping_size` bytes long, which is large enough to hold a
+        // `ChunkHeader` because `allocate`, the only caller, always adds
+        // `size_of::<ChunkHeader>()` to `min_size` on top of its layout.
+        unsafe {
+            header_ptr.write(ChunkHeader {
+                next: self.head.get(),
+                mapping_start: mapping_start.as_ptr() as usize,
+                mapping_size,
+                data_end: mapping_start.as_ptr() as usize + mapping_size,
+                cursor: mapping_start.as_ptr() as usize + std::mem::size_of::<ChunkHeader>(),
+                last_alloc: None,
+            });
+        }
+        // SAFETY: `header_ptr` was just written to and is non-null because
+        // `mapping_start` is non-null.
+        let header = unsafe { NonNull::new_unchecked(header_ptr) };
+        self.head.set(Some(header));
+        Ok(header)
+    }
+}
+
+impl Default for PageAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Attempts to bump-allocate `layout` out of `header`'s remaining space,
+/// returning `None` if it doesn't fit.
+///
+/// # Safety
+///
+/// `header` must point at a live, fully initialized [`ChunkHeader`].
+unsafe fn try_bump(header: &mut ChunkHeader, layout: std::alloc::Layout) -> Option<NonNull<[u8]>> {
+    let align = layout.align();
+    let aligned = (header.cursor + align - 1) & !(align - 1);
+    let new_cursor = aligned.checked_add(layout.size())?;
+    if new_cursor > header.data_end {
+        return None;
+    }
+    header.cursor = new_cursor;
+    header.last_alloc = Some((aligned, layout.size()));
+
+    let slice = std::ptr::slice_from_raw_parts_mut(aligned as *mut u8, layout.size());
+    // SAFETY: `aligned` lies within the chunk's reserved mapping (checked
+    // above) and is non-null because it is derived from the non-null
+    // mapping base.
+    Some(unsafe { NonNull::new_unchecked(slice) })
+}
+
+fn round_up(value: usize, multiple: usize) -> usize {
+    let remainder = value % multiple;
+    if remainder == 0 {
+        value
+    } else {
+        value + (multiple - remainder)
+    }
+}
+
+impl CustomAllocator for PageAllocator {
+    type Error = PageAllocatorError;
+
+    fn allocate(&self, layout: std::alloc::Layout) -> Result<NonNull<[u8]>, Self::Error> {
+        if let Some(mut head) = self.head.get() {
+            // SAFETY: the head, if present, always points at a live chunk
+            // owned by this allocator.
+            if let Some(ptr) = unsafe { try_bump(head.as_mut(), layout) } {
+                return Ok(ptr);
+            }
+        }
+
+        let min_size = std::mem::size_of::<ChunkHeader>()
+            .saturating_add(layout.size())
+            .saturating_add(layout.align());
+        let mut header = self.reserve_chunk(min_size)?;
+        // SAFETY: the chunk we just reserved was sized to fit `layout`
+        // plus header and alignment overhead.
+        let ptr = unsafe { try_bump(header.as_mut(), layout) }
+            .expect("freshly reserved chunk must fit the requested layout");
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: std::alloc::Layout) {
+        // Bump allocators reclaim everything at once, on `Drop`.
+    }
+
+    #[allow(clippy::result_unit_err)] // matches `CustomAllocator::grow_in_place`'s sentinel `Err(())`.
+    unsafe fn grow_in_place(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: std::alloc::Layout,
+        new_layout: std::alloc::Layout,
+    ) -> Result<NonNull<[u8]>, ()> {
+        debug_assert!(new_layout.size() >= old_layout.size());
+
+        // The block's address was only ever chosen to satisfy
+        // `old_layout.align()`. `grow`'s contract allows `new_layout` to
+        // ask for a stricter alignment, which extending in place cannot
+        // provide without moving the block, so decline and let the caller
+        // fall back to allocate-and-copy.
+        if new_layout.align() > old_layout.align()
+            || !(ptr.as_ptr() as usize).is_multiple_of(new_layout.align())
+        {
+            return Err(());
+        }
+
+        let mut head = self.head.get().ok_or(())?;
+        // SAFETY: the head, if present, always points at a live chunk
+        // owned by this allocator.
+        let header = unsafe { head.as_mut() };
+        if header.last_alloc != Some((ptr.as_ptr() as usize, old_layout.size())) {
+            return Err(());
+        }
+
+        let extra = new_layout.size() - old_layout.size();
+        let new_cursor = header.cursor + extra;
+        if new_cursor > header.data_end {
+            return Err(());
+        }
+
+        header.cursor = new_cursor;
+        header.last_alloc = Some((ptr.as_ptr() as usize, new_layout.size()));
+        let slice = std::ptr::slice_from_raw_parts_mut(ptr.as_ptr(), new_layout.size());
+        // SAFETY: `ptr` is non-null and the region up to `new_cursor` was
+        // just reserved in this chunk.
+        Ok(unsafe { NonNull::new_unchecked(slice) })
+    }
+}
+
+impl Drop for PageAllocator {
+    fn drop(&mut self) {
+        let mut current = self.head.get();
+        while let Some(header) = current {
+            // SAFETY: every chunk in the list is a live reservation owned
+            // by this allocator until it is unmapped here.
+            let (mapping_start, mapping_size, next) = unsafe {
+                let header = header.as_ref();
+                (header.mapping_start, header.mapping_size, header.next)
+            };
+            // SAFETY: `mapping_start`/`mapping_size` describe exactly the
+            // reservation made for this chunk in `reserve_chunk`, and this
+            // is the only place that releases it.
+            unsafe {
+                os::release(mapping_start, mapping_size);
+            }
+            current = next;
+        }
+    }
+}
+
+#[cfg(unix)]
+mod os {
+    use std::ffi::c_void;
+    use std::ptr::{self, NonNull};
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: i32,
+            flags: i32,
+            fd: i32,
+            offset: i64,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> i32;
+        fn sysconf(name: i32) -> i64;
+    }
+
+    const PROT_READ: i32 = 1;
+    const PROT_WRITE: i32 = 2;
+    const MAP_PRIVATE: i32 = 0x0002;
+    #[cfg(target_os = "linux")]
+    const MAP_ANONYMOUS: i32 = 0x20;
+    #[cfg(target_os = "macos")]
+    const MAP_ANONYMOUS: i32 = 0x1000;
+    #[cfg(target_os = "linux")]
+    const SC_PAGESIZE: i32 = 30;
+    #[cfg(target_os = "macos")]
+    const SC_PAGESIZE: i32 = 29;
+
+    pub fn page_size() -> usize {
+        // SAFETY: `sysconf` with a valid `name` is always safe to call.
+        let size = unsafe { sysconf(SC_PAGESIZE) };
+        if size > 0 {
+            size as usize
+        } else {
+            4096
+        }
+    }
+
+    pub fn reserve(size: usize) -> Option<NonNull<u8>> {
+        // SAFETY: requesting an anonymous, private mapping of `size`
+        // bytes is always a valid `mmap` call; the result is checked for
+        // failure below.
+        let ptr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                size,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == usize::MAX as *mut c_void {
+            None
+        } else {
+            NonNull::new(ptr.cast::<u8>())
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `addr`/`size` must describe a mapping previously returned by
+    /// [`reserve`] that has not yet been released.
+    pub unsafe fn release(addr: usize, size: usize) {
+        munmap(addr as *mut c_void, size);
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use std::ffi::c_void;
+    use std::ptr::NonNull;
+
+    #[repr(C)]
+    struct SystemInfo {
+        processor_architecture: u16,
+        reserved: u16,
+        page_size: u32,
+        minimum_application_address: *mut c_void,
+        maximum_application_address: *mut c_void,
+        active_processor_mask: usize,
+        number_of_processors: u32,
+        processor_type: u32,
+        allocation_granularity: u32,
+        processor_level: u16,
+        processor_revision: u16,
+    }
+
+    extern "system" {
+        fn VirtualAlloc(
+            lp_address: *mut c_void,
+            dw_size: usize,
+            fl_allocation_type: u32,
+            fl_protect: u32,
+        ) -> *mut c_void;
+        fn VirtualFree(lp_address: *mut c_void, dw_size: usize, dw_free_type: u32) -> i32;
+        fn GetSystemInfo(lp_system_info: *mut SystemInfo);
+    }
+
+    const MEM_COMMIT: u32 = 0x1000;
+    const MEM_RESERVE: u32 = 0x2000;
+    const MEM_RELEASE: u32 = 0x8000;
+    const PAGE_READWRITE: u32 = 0x04;
+
+    pub fn page_size() -> usize {
+        let mut info: SystemInfo = unsafe { std::mem::zeroed() };
+        // SAFETY: `info` is a valid, writable `SystemInfo` for the call to
+        // populate.
+        unsafe { GetSystemInfo(&mut info) };
+        if info.page_size > 0 {
+            info.page_size as usize
+        } else {
+            4096
+        }
+    }
+
+    pub fn reserve(size: usize) -> Option<NonNull<u8>> {
+        // SAFETY: requesting a fresh committed reservation of `size` bytes
+        // is always a valid `VirtualAlloc` call; the result is checked for
+        // failure below.
+        let ptr = unsafe {
+            VirtualAlloc(
+                std::ptr::null_mut(),
+                size,
+                MEM_COMMIT | MEM_RESERVE,
+                PAGE_READWRITE,
+            )
+        };
+        NonNull::new(ptr.cast::<u8>())
+    }
+
+    /// # Safety
+    ///
+    /// `addr`/`size` must describe a mapping previously returned by
+    /// [`reserve`] that has not yet been released.
+    pub unsafe fn release(addr: usize, _size: usize) {
+        VirtualFree(addr as *mut c_void, 0, MEM_RELEASE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::Layout;
+
+    #[test]
+    fn allocates_aligned_and_writable_memory() {
+        let allocator = PageAllocator::new();
+        let layout = Layout::from_size_align(64, 16).unwrap();
+        let ptr = allocator.allocate(layout).unwrap();
+        assert!(ptr.len() >= layout.size());
+        assert!((ptr.as_ptr().cast::<u8>() as usize).is_multiple_of(layout.align()));
+        // SAFETY: `ptr` is a fresh allocation of at least `layout.size()`
+        // writable bytes.
+        unsafe {
+            ptr.as_ptr().cast::<u8>().write_bytes(0xAB, layout.size());
+        }
+    }
+
+    #[test]
+    fn bumps_within_a_single_chunk() {
+        let allocator = PageAllocator::new();
+        let layout = Layout::from_size_align(8, 8).unwrap();
+        let first = allocator.allocate(layout).unwrap();
+        let second = allocator.allocate(layout).unwrap();
+        assert_eq!(
+            second.as_ptr().cast::<u8>() as usize,
+            first.as_ptr().cast::<u8>() as usize + layout.size()
+        );
+    }
+
+    #[test]
+    fn reserves_a_new_chunk_once_the_first_is_exhausted() {
+        let allocator = PageAllocator::with_chunk_size(1);
+        let layout = Layout::from_size_align(8, 8).unwrap();
+        allocator.allocate(layout).unwrap();
+        let first_chunk = allocator.head.get();
+        // The first chunk was rounded up to a single page, part of which
+        // is already taken by its header and the allocation above, so a
+        // page-sized request forces a second OS reservation.
+        let page = Layout::from_size_align(os::page_size(), 8).unwrap();
+        allocator.allocate(page).unwrap();
+        assert_ne!(allocator.head.get(), first_chunk);
+    }
+
+    #[test]
+    fn max_reservation_rejects_oversized_chunks() {
+        let allocator = PageAllocator::with_chunk_size(1).with_max_reservation(1);
+        let layout = Layout::from_size_align(8, 8).unwrap();
+        assert!(matches!(
+            allocator.allocate(layout),
+            Err(PageAllocatorError::MaxReservationExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn grow_in_place_extends_the_most_recent_allocation() {
+        let allocator = PageAllocator::new();
+        let old_layout = Layout::from_size_align(8, 8).unwrap();
+        let ptr = allocator.allocate(old_layout).unwrap();
+        let new_layout = Layout::from_size_align(16, 8).unwrap();
+        // SAFETY: `ptr` was just allocated with `old_layout` from this same
+        // allocator, and is still its most recent allocation.
+        let grown =
+            unsafe { allocator.grow_in_place(ptr.cast::<u8>(), old_layout, new_layout) }.unwrap();
+        assert_eq!(grown.cast::<u8>(), ptr.cast::<u8>());
+        assert_eq!(grown.len(), new_layout.size());
+    }
+
+    #[test]
+    fn grow_in_place_declines_a_stricter_alignment() {
+        let allocator = PageAllocator::new();
+        let old_layout = Layout::from_size_align(8, 8).unwrap();
+        let ptr = allocator.allocate(old_layout).unwrap();
+        let new_layout = Layout::from_size_align(8, 64).unwrap();
+        // SAFETY: `ptr` was just allocated with `old_layout` from this same
+        // allocator.
+        let result = unsafe { allocator.grow_in_place(ptr.cast::<u8>(), old_layout, new_layout) };
+        assert_eq!(result, Err(()));
+    }
+
+    #[test]
+    fn grow_in_place_declines_once_another_allocation_follows() {
+        let allocator = PageAllocator::new();
+        let layout = Layout::from_size_align(8, 8).unwrap();
+        let ptr = allocator.allocate(layout).unwrap();
+        allocator.allocate(layout).unwrap();
+        // SAFETY: `ptr` was allocated with `layout` from this same
+        // allocator, even though it's no longer the most recent one.
+        let result = unsafe { allocator.grow_in_place(ptr.cast::<u8>(), layout, layout) };
+        assert_eq!(result, Err(()));
+    }
+
+    #[test]
+    fn round_up_rounds_to_the_next_multiple() {
+        assert_eq!(round_up(0, 4096), 0);
+        assert_eq!(round_up(1, 4096), 4096);
+        assert_eq!(round_up(4096, 4096), 4096);
+        assert_eq!(round_up(4097, 4096), 8192);
+    }
+}
@@ -0,0 +1,288 @@
+//! A fallible, capacity-owning buffer parameterized over [`CustomAllocator`].
+//!
+//! [`RawVec`] is the growth engine behind [`Vec`][crate::vec::Vec]: it owns
+//! a `T`-typed allocation and knows how to grow it, but has no notion of
+//! length. Every growth path returns a [`TryReserveError`] instead of
+//! aborting, which is the point of giving [`CustomAllocator`] a typed
+//! `Error` in the first place.
+
+use crate::CustomAllocator;
+use std::alloc::Layout;
+use std::fmt;
+use std::mem;
+use std::ptr::NonNull;
+
+/// The error returned when growing a [`RawVec`] fails.
+pub enum TryReserveError<E> {
+    /// The requested capacity, in elements, would overflow `isize::MAX`
+    /// bytes once multiplied by `size_of::<T>()`.
+    CapacityOverflow,
+    /// The underlying allocator failed to satisfy the request.
+    AllocError {
+        /// The layout that was requested from the allocator.
+        layout: Layout,
+        /// The error returned by the allocator.
+        source: E,
+    },
+}
+
+impl<E: fmt::Debug> fmt::Debug for TryReserveError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CapacityOverflow => f.debug_struct("CapacityOverflow").finish(),
+            Self::AllocError { layout, source } => f
+                .debug_struct("AllocError")
+                .field("layout", layout)
+                .field("source", source)
+                .finish(),
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for TryReserveError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CapacityOverflow => {
+                write!(f, "capacity overflow: requested size exceeds `isize::MAX`")
+            }
+            Self::AllocError { layout, source } => write!(
+                f,
+                "allocator failed to allocate {} bytes (align {}): {source:?}",
+                layout.size(),
+                layout.align()
+            ),
+        }
+    }
+}
+
+impl<E: fmt::Debug> std::error::Error for TryReserveError<E> {}
+
+/// Computes the [`Layout`] for an array of `cap` elements of `T`,
+/// checking for overflow before ever consulting the allocator.
+fn array_layout<T, E>(cap: usize) -> Result<Layout, TryReserveError<E>> {
+    let size = cap
+        .checked_mul(mem::size_of::<T>())
+        .ok_or(TryReserveError::CapacityOverflow)?;
+    if size > isize::MAX as usize {
+        return Err(TryReserveError::CapacityOverflow);
+    }
+    Layout::array::<T>(cap).map_err(|_| TryReserveError::CapacityOverflow)
+}
+
+/// The capacity a freshly grown `RawVec` should jump to when the caller
+/// hasn't asked for anything bigger, mirroring `std`'s own amortized
+/// growth heuristic.
+fn min_non_zero_cap(elem_size: usize) -> usize {
+    if elem_size == 1 {
+        8
+    } else if elem_size <= 1024 {
+        4
+    } else {
+        1
+    }
+}
+
+/// An allocation of zero or more `T`s, owned and grown through a
+/// [`CustomAllocator`].
+///
+/// `RawVec` tracks capacity only; it has no concept of length and does
+/// not drop its elements. It exists to be embedded in higher-level
+/// containers like [`Vec`][crate::vec::Vec].
+pub struct RawVec<T, A: CustomAllocator> {
+    ptr: NonNull<T>,
+    cap: usize,
+    alloc: A,
+}
+
+impl<T, A: CustomAllocator> RawVec<T, A> {
+    /// Creates an empty `RawVec` that allocates through `alloc`.
+    ///
+    /// No allocation happens until the first call to a `try_reserve*`
+    /// method.
+    pub fn new_in(alloc: A) -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            cap: if mem::size_of::<T>() == 0 { usize::MAX } else { 0 },
+            alloc,
+        }
+    }
+
+    /// Creates a `RawVec` with space for at least `capacity` elements.
+    pub fn try_with_capacity_in(
+        capacity: usize,
+        alloc: A,
+    ) -> Result<Self, TryReserveError<A::Error>> {
+        let mut raw = Self::new_in(alloc);
+        if capacity > 0 {
+            raw.try_reserve_exact(0, capacity)?;
+        }
+        Ok(raw)
+    }
+
+    /// The raw pointer to the start of the allocation.
+    ///
+    /// Dangling (but well-aligned) when [`Self::capacity`] is zero.
+    pub fn ptr(&self) -> NonNull<T> {
+        self.ptr
+    }
+
+    /// The number of `T`s this allocation can hold.
+    pub fn capacity(&self) -> usize {
+        if mem::size_of::<T>() == 0 { usize::MAX } else { self.cap }
+    }
+
+    /// The allocator backing this `RawVec`.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    fn current_layout(&self) -> Option<Layout> {
+        if mem::size_of::<T>() == 0 || self.cap == 0 {
+            None
+        } else {
+            // Never fails: it already fit when this capacity was set.
+            Layout::array::<T>(self.cap).ok()
+        }
+    }
+
+    fn needs_to_grow(&self, len: usize, additional: usize) -> bool {
+        additional > self.capacity().wrapping_sub(len)
+    }
+
+    /// Ensures there is room for at least `additional` more elements past
+    /// `len`, growing by doubling (amortized) if not.
+    pub fn try_reserve(
+        &mut self,
+        len: usize,
+        additional: usize,
+    ) -> Result<(), TryReserveError<A::Error>> {
+        if self.needs_to_grow(len, additional) {
+            self.grow_amortized(len, additional)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Ensures there is room for exactly `len + additional` elements,
+    /// growing by the precise amount requested if not.
+    pub fn try_reserve_exact(
+        &mut self,
+        len: usize,
+        additional: usize,
+    ) -> Result<(), TryReserveError<A::Error>> {
+        if self.needs_to_grow(len, additional) {
+            let required = len
+                .checked_add(additional)
+                .ok_or(TryReserveError::CapacityOverflow)?;
+            self.set_capacity(required)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn grow_amortized(
+        &mut self,
+        len: usize,
+        additional: usize,
+    ) -> Result<(), TryReserveError<A::Error>> {
+        let required = len
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        let doubled = self.cap.saturating_mul(2);
+        let new_cap = required
+            .max(doubled)
+            .max(min_non_zero_cap(mem::size_of::<T>()));
+        self.set_capacity(new_cap)
+    }
+
+    fn set_capacity(&mut self, new_cap: usize) -> Result<(), TryReserveError<A::Error>> {
+        if mem::size_of::<T>() == 0 {
+            self.cap = new_cap;
+            return Ok(());
+        }
+
+        let new_layout = array_layout::<T, A::Error>(new_cap)?;
+        let new_ptr = match self.current_layout() {
+            None => self.alloc.allocate(new_layout),
+            // SAFETY: `self.ptr` was allocated by `self.alloc` with
+            // `old_layout`, and `new_layout.size() >= old_layout.size()`
+            // because capacity only ever grows here.
+            Some(old_layout) => unsafe {
+                self.alloc
+                    .grow(self.ptr.cast(), old_layout, new_layout)
+            },
+        }
+        .map_err(|source| TryReserveError::AllocError {
+            layout: new_layout,
+            source,
+        })?;
+
+        self.ptr = new_ptr.cast();
+        self.cap = new_cap;
+        Ok(())
+    }
+}
+
+impl<T, A: CustomAllocator> Drop for RawVec<T, A> {
+    fn drop(&mut self) {
+        if let Some(layout) = self.current_layout() {
+            // SAFETY: `self.ptr`/`layout` describe exactly the allocation
+            // made in `set_capacity`, and this is the only place it is
+            // released.
+            unsafe {
+                self.alloc.deallocate(self.ptr.cast(), layout);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::System;
+
+    #[test]
+    fn new_in_does_not_allocate() {
+        let raw = RawVec::<u8, System>::new_in(System);
+        assert_eq!(raw.capacity(), 0);
+    }
+
+    #[test]
+    fn try_with_capacity_in_reserves_up_front() {
+        let raw = RawVec::<u32, System>::try_with_capacity_in(4, System).unwrap();
+        assert!(raw.capacity() >= 4);
+    }
+
+    #[test]
+    fn try_reserve_grows_by_amortized_doubling() {
+        let mut raw = RawVec::<u32, System>::new_in(System);
+        raw.try_reserve(0, 1).unwrap();
+        let first_cap = raw.capacity();
+        assert!(first_cap >= 1);
+
+        raw.try_reserve(first_cap, 1).unwrap();
+        assert!(raw.capacity() > first_cap);
+    }
+
+    #[test]
+    fn try_reserve_exact_grows_by_the_precise_amount() {
+        let mut raw = RawVec::<u32, System>::new_in(System);
+        raw.try_reserve_exact(0, 3).unwrap();
+        assert_eq!(raw.capacity(), 3);
+    }
+
+    #[test]
+    fn zero_sized_types_never_need_to_allocate() {
+        let mut raw = RawVec::<(), System>::new_in(System);
+        assert_eq!(raw.capacity(), usize::MAX);
+        raw.try_reserve(0, usize::MAX).unwrap();
+        assert_eq!(raw.capacity(), usize::MAX);
+    }
+
+    #[test]
+    fn capacity_overflow_is_reported_without_touching_the_allocator() {
+        let mut raw = RawVec::<u32, System>::new_in(System);
+        let err = raw.try_reserve_exact(0, usize::MAX).unwrap_err();
+        assert!(matches!(err, TryReserveError::CapacityOverflow));
+    }
+}
@@ -0,0 +1,310 @@
+//! A [`CustomAllocator`] wrapper that tracks allocation statistics.
+//!
+//! [`TrackingAllocator`] forwards every call to an inner allocator while
+//! maintaining a handful of atomic counters, and implements
+//! [`MemoryConsumption`] itself so it can back a cheap, always-accurate
+//! `MEMORY USAGE`-style report without walking the whole object graph.
+
+use crate::{CustomAllocator, MemoryConsumption};
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Wraps `A`, counting bytes and calls flowing through it.
+///
+/// `allocate`, `allocate_zeroed`, `deallocate`, `grow`, `grow_zeroed` and
+/// `shrink` are forwarded to the inner allocator unchanged, with only the
+/// accounting added on top. [`CustomAllocator::grow_in_place`] and
+/// [`CustomAllocator::shrink_in_place`] are not forwarded: called on the
+/// wrapper directly they always decline, although `grow`/`shrink` still
+/// reach the inner allocator's own in-place paths.
+///
+/// Because [`CustomAllocator::deallocate`] and [`CustomAllocator::shrink`]
+/// only ever receive the *original* [`Layout`], not the actual (possibly
+/// larger) size the allocator granted, every counter here is kept in
+/// terms of requested [`Layout`] sizes rather than granted slice lengths
+/// — crediting the returned slice's (possibly larger) length would leave
+/// no way to debit the same amount back on `deallocate`/`shrink`, and
+/// [`Self::live_bytes`] would drift upward forever instead of returning
+/// to zero once nothing is live.
+pub struct TrackingAllocator<A: CustomAllocator> {
+    inner: A,
+    live_bytes: AtomicUsize,
+    peak_live_bytes: AtomicUsize,
+    total_allocations: AtomicUsize,
+    total_deallocations: AtomicUsize,
+    #[cfg(feature = "size_class_histogram")]
+    histogram: SizeClassHistogram,
+}
+
+impl<A: CustomAllocator> TrackingAllocator<A> {
+    /// Wraps `inner`, starting all counters at zero.
+    pub fn new(inner: A) -> Self {
+        Self {
+            inner,
+            live_bytes: AtomicUsize::new(0),
+            peak_live_bytes: AtomicUsize::new(0),
+            total_allocations: AtomicUsize::new(0),
+            total_deallocations: AtomicUsize::new(0),
+            #[cfg(feature = "size_class_histogram")]
+            histogram: SizeClassHistogram::new(),
+        }
+    }
+
+    /// The wrapped allocator.
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Unwraps this `TrackingAllocator`, discarding its counters.
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+
+    /// The number of bytes currently live (allocated but not yet
+    /// deallocated).
+    pub fn live_bytes(&self) -> usize {
+        self.live_bytes.load(Ordering::Relaxed)
+    }
+
+    /// The highest value [`Self::live_bytes`] has ever reached.
+    pub fn peak_live_bytes(&self) -> usize {
+        self.peak_live_bytes.load(Ordering::Relaxed)
+    }
+
+    /// The total number of successful `allocate`/`allocate_zeroed` calls
+    /// (each successful `grow`/`shrink` also counts as one allocation and
+    /// one deallocation).
+    pub fn total_allocations(&self) -> usize {
+        self.total_allocations.load(Ordering::Relaxed)
+    }
+
+    /// The total number of `deallocate` calls (see
+    /// [`Self::total_allocations`] for how `grow`/`shrink` are counted).
+    pub fn total_deallocations(&self) -> usize {
+        self.total_deallocations.load(Ordering::Relaxed)
+    }
+
+    /// A snapshot of the per-size-class allocation counts, indexed by
+    /// `usize::BITS - size.leading_zeros()` (i.e. the bit-length of
+    /// `size`; bucket 0 holds only `size == 0`, bucket 1 holds `size == 1`,
+    /// bucket 2 holds `size` in `2..=3`, bucket 3 holds `4..=7`, and so on).
+    ///
+    /// Only available when built with the `size_class_histogram` feature.
+    #[cfg(feature = "size_class_histogram")]
+    pub fn size_class_histogram(&self) -> [usize; SizeClassHistogram::BUCKETS] {
+        self.histogram.counts()
+    }
+
+    fn record_alloc(&self, size: usize) {
+        let live = self.live_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak_live_bytes.fetch_max(live, Ordering::Relaxed);
+        self.total_allocations.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "size_class_histogram")]
+        self.histogram.record(size);
+    }
+
+    fn record_dealloc(&self, size: usize) {
+        self.live_bytes.fetch_sub(size, Ordering::Relaxed);
+        self.total_deallocations.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl<A: CustomAllocator> CustomAllocator for TrackingAllocator<A> {
+    type Error = A::Error;
+
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, Self::Error> {
+        let ptr = self.inner.allocate(layout)?;
+        self.record_alloc(layout.size());
+        Ok(ptr)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, Self::Error> {
+        let ptr = self.inner.allocate_zeroed(layout)?;
+        self.record_alloc(layout.size());
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // SAFETY: forwarding this call's own safety contract to the inner
+        // allocator.
+        unsafe {
+            self.inner.deallocate(ptr, layout);
+        }
+        self.record_dealloc(layout.size());
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, Self::Error> {
+        // SAFETY: forwarding this call's own safety contract to the inner
+        // allocator.
+        let new_ptr = unsafe { self.inner.grow(ptr, old_layout, new_layout) }?;
+        self.record_dealloc(old_layout.size());
+        self.record_alloc(new_layout.size());
+        Ok(new_ptr)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, Self::Error> {
+        // SAFETY: forwarding this call's own safety contract to the inner
+        // allocator.
+        let new_ptr = unsafe { self.inner.grow_zeroed(ptr, old_layout, new_layout) }?;
+        self.record_dealloc(old_layout.size());
+        self.record_alloc(new_layout.size());
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, Self::Error> {
+        // SAFETY: forwarding this call's own safety contract to the inner
+        // allocator.
+        let new_ptr = unsafe { self.inner.shrink(ptr, old_layout, new_layout) }?;
+        self.record_dealloc(old_layout.size());
+        self.record_alloc(new_layout.size());
+        Ok(new_ptr)
+    }
+}
+
+impl<A: CustomAllocator> MemoryConsumption for TrackingAllocator<A> {
+    fn memory_consumption(&self) -> usize {
+        self.live_bytes()
+    }
+}
+
+/// A per-size-class histogram of allocation counts, bucketed by
+/// power-of-two size class.
+#[cfg(feature = "size_class_histogram")]
+pub struct SizeClassHistogram {
+    buckets: [AtomicUsize; Self::BUCKETS],
+}
+
+#[cfg(feature = "size_class_histogram")]
+impl SizeClassHistogram {
+    /// The number of size-class buckets, covering allocations up to
+    /// `2.pow(BUCKETS - 1)` bytes.
+    pub const BUCKETS: usize = 64;
+
+    fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicUsize::new(0)),
+        }
+    }
+
+    fn bucket_for(size: usize) -> usize {
+        let class = usize::BITS - size.leading_zeros();
+        (class as usize).min(Self::BUCKETS - 1)
+    }
+
+    fn record(&self, size: usize) {
+        self.buckets[Self::bucket_for(size)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn counts(&self) -> [usize; Self::BUCKETS] {
+        std::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::System;
+
+    #[test]
+    fn allocate_and_deallocate_returns_live_bytes_to_zero() {
+        let tracking = TrackingAllocator::new(System);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptr = tracking.allocate(layout).unwrap();
+
+        assert_eq!(tracking.live_bytes(), 64);
+        assert_eq!(tracking.peak_live_bytes(), 64);
+        assert_eq!(tracking.total_allocations(), 1);
+        assert_eq!(tracking.total_deallocations(), 0);
+
+        // SAFETY: `ptr` was just allocated with `layout` from this same
+        // allocator.
+        unsafe {
+            tracking.deallocate(ptr.cast::<u8>(), layout);
+        }
+
+        assert_eq!(tracking.live_bytes(), 0);
+        assert_eq!(tracking.peak_live_bytes(), 64);
+        assert_eq!(tracking.total_deallocations(), 1);
+    }
+
+    #[test]
+    fn grow_debits_the_old_layout_and_credits_the_new_one() {
+        let tracking = TrackingAllocator::new(System);
+        let old_layout = Layout::from_size_align(8, 8).unwrap();
+        let ptr = tracking.allocate(old_layout).unwrap();
+        assert_eq!(tracking.live_bytes(), 8);
+
+        let new_layout = Layout::from_size_align(256, 8).unwrap();
+        // SAFETY: `ptr` was just allocated with `old_layout` from this
+        // same allocator.
+        let grown = unsafe { tracking.grow(ptr.cast::<u8>(), old_layout, new_layout) }.unwrap();
+
+        // Regression test: crediting the (possibly larger) granted slice
+        // length instead of `new_layout.size()` here would leave
+        // `live_bytes` permanently inflated, since `deallocate` only ever
+        // debits the requested `Layout::size()`.
+        assert_eq!(tracking.live_bytes(), new_layout.size());
+
+        // SAFETY: `grown` was just returned for `new_layout` from this
+        // same allocator.
+        unsafe {
+            tracking.deallocate(grown.cast::<u8>(), new_layout);
+        }
+        assert_eq!(tracking.live_bytes(), 0);
+    }
+
+    #[test]
+    fn peak_live_bytes_tracks_the_high_water_mark() {
+        let tracking = TrackingAllocator::new(System);
+        let small = Layout::from_size_align(8, 8).unwrap();
+        let big = Layout::from_size_align(128, 8).unwrap();
+
+        let a = tracking.allocate(big).unwrap();
+        let b = tracking.allocate(small).unwrap();
+        assert_eq!(tracking.peak_live_bytes(), 136);
+
+        // SAFETY: `a` was allocated with `big` from this same allocator.
+        unsafe { tracking.deallocate(a.cast::<u8>(), big) };
+        assert_eq!(tracking.live_bytes(), 8);
+        assert_eq!(tracking.peak_live_bytes(), 136);
+
+        // SAFETY: `b` was allocated with `small` from this same allocator.
+        unsafe { tracking.deallocate(b.cast::<u8>(), small) };
+    }
+
+    #[cfg(feature = "size_class_histogram")]
+    #[test]
+    fn histogram_buckets_by_bit_length() {
+        assert_eq!(SizeClassHistogram::bucket_for(0), 0);
+        assert_eq!(SizeClassHistogram::bucket_for(1), 1);
+        assert_eq!(SizeClassHistogram::bucket_for(2), 2);
+        assert_eq!(SizeClassHistogram::bucket_for(3), 2);
+        assert_eq!(SizeClassHistogram::bucket_for(4), 3);
+        assert_eq!(SizeClassHistogram::bucket_for(7), 3);
+
+        let tracking = TrackingAllocator::new(System);
+        let layout = Layout::from_size_align(4, 4).unwrap();
+        let ptr = tracking.allocate(layout).unwrap();
+        let histogram = tracking.size_class_histogram();
+        assert_eq!(histogram[3], 1);
+        // SAFETY: `ptr` was just allocated with `layout` from this same
+        // allocator.
+        unsafe { tracking.deallocate(ptr.cast::<u8>(), layout) };
+    }
+}
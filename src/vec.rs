@@ -0,0 +1,163 @@
+//! A fallible `Vec<T>`, parameterized over [`CustomAllocator`].
+//!
+//! Unlike `std::vec::Vec`, every growth-inducing operation here returns a
+//! [`TryReserveError`] rather than aborting on OOM, so callers (Redis data
+//! structures, in particular) can react to allocator failure instead of
+//! crashing the process.
+
+use crate::raw_vec::{RawVec, TryReserveError};
+use crate::CustomAllocator;
+use std::ops::{Deref, DerefMut};
+
+/// A contiguous, growable array of `T`, allocated through `A`.
+///
+/// All growth goes through fallible `try_*` methods; there is no
+/// infallible `push` or `reserve`.
+pub struct Vec<T, A: CustomAllocator> {
+    raw: RawVec<T, A>,
+    len: usize,
+}
+
+impl<T, A: CustomAllocator> Vec<T, A> {
+    /// Creates an empty `Vec` that allocates through `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        Self {
+            raw: RawVec::new_in(alloc),
+            len: 0,
+        }
+    }
+
+    /// Creates an empty `Vec` with space for at least `capacity` elements.
+    pub fn try_with_capacity_in(
+        capacity: usize,
+        alloc: A,
+    ) -> Result<Self, TryReserveError<A::Error>> {
+        Ok(Self {
+            raw: RawVec::try_with_capacity_in(capacity, alloc)?,
+            len: 0,
+        })
+    }
+
+    /// The number of elements currently stored.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether this `Vec` holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of elements this `Vec` can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.raw.capacity()
+    }
+
+    /// The allocator backing this `Vec`.
+    pub fn allocator(&self) -> &A {
+        self.raw.allocator()
+    }
+
+    /// Reserves capacity for at least `additional` more elements,
+    /// growing by amortized doubling.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError<A::Error>> {
+        self.raw.try_reserve(self.len, additional)
+    }
+
+    /// Reserves capacity for exactly `additional` more elements.
+    pub fn try_reserve_exact(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), TryReserveError<A::Error>> {
+        self.raw.try_reserve_exact(self.len, additional)
+    }
+
+    /// Appends `value`, growing the backing allocation first if needed.
+    pub fn try_push(&mut self, value: T) -> Result<(), TryReserveError<A::Error>> {
+        if self.len == self.raw.capacity() {
+            self.try_reserve(1)?;
+        }
+        // SAFETY: the reservation above guarantees `self.len` is a valid,
+        // unoccupied index within the backing allocation.
+        unsafe {
+            self.raw.ptr().as_ptr().add(self.len).write(value);
+        }
+        self.len += 1;
+        Ok(())
+    }
+}
+
+impl<T, A: CustomAllocator> Deref for Vec<T, A> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the first `self.len` elements of the backing allocation
+        // are initialized by `try_push`.
+        unsafe { std::slice::from_raw_parts(self.raw.ptr().as_ptr(), self.len) }
+    }
+}
+
+impl<T, A: CustomAllocator> DerefMut for Vec<T, A> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: the first `self.len` elements of the backing allocation
+        // are initialized by `try_push`.
+        unsafe { std::slice::from_raw_parts_mut(self.raw.ptr().as_ptr(), self.len) }
+    }
+}
+
+impl<T, A: CustomAllocator> Drop for Vec<T, A> {
+    fn drop(&mut self) {
+        // SAFETY: `self` (via `DerefMut`) points at exactly the `self.len`
+        // initialized elements owned by this `Vec`.
+        unsafe {
+            std::ptr::drop_in_place(self.deref_mut() as *mut [T]);
+        }
+        // The backing allocation itself is released by `RawVec::drop`.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::System;
+    use std::cell::Cell;
+
+    #[test]
+    fn try_push_appends_and_grows_as_needed() {
+        let mut vec = Vec::<u32, System>::new_in(System);
+        assert!(vec.is_empty());
+        for i in 0..10 {
+            vec.try_push(i).unwrap();
+        }
+        assert_eq!(vec.len(), 10);
+        assert!(vec.capacity() >= 10);
+        assert_eq!(&*vec, &(0..10).collect::<std::vec::Vec<_>>()[..]);
+    }
+
+    #[test]
+    fn drop_runs_every_pushed_element_exactly_once() {
+        thread_local! {
+            static DROPS: Cell<usize> = const { Cell::new(0) };
+        }
+        struct CountsDrops;
+        impl Drop for CountsDrops {
+            fn drop(&mut self) {
+                DROPS.with(|drops| drops.set(drops.get() + 1));
+            }
+        }
+
+        let mut vec = Vec::<CountsDrops, System>::new_in(System);
+        for _ in 0..5 {
+            vec.try_push(CountsDrops).unwrap();
+        }
+        drop(vec);
+        DROPS.with(|drops| assert_eq!(drops.get(), 5));
+    }
+
+    #[test]
+    fn try_with_capacity_in_starts_empty_but_reserved() {
+        let vec = Vec::<u32, System>::try_with_capacity_in(8, System).unwrap();
+        assert!(vec.is_empty());
+        assert!(vec.capacity() >= 8);
+    }
+}